@@ -0,0 +1,137 @@
+//! A borrowed read buffer, split into a filled-and-initialized prefix and
+//! an uninitialized-but-reserved tail.
+//!
+//! `Terminal::read` used to expose the uninitialized capacity of a
+//! `Vec<u8>` to `read(2)`/`ReadFile` by calling `set_len` past the
+//! initialized length and fixing it up afterward once the syscall
+//! returned. Every backend had to get that unsafe dance right on its
+//! own. `ReadBuffer` does it once, safely: `unfilled()` hands the OS the
+//! raw, possibly-uninitialized tail to write into, and `advance` marks
+//! however many bytes it actually produced as filled, without touching
+//! memory the backend never asked the OS to write.
+
+use std::mem::MaybeUninit;
+use std::slice;
+
+/// A cursor over a `&mut [MaybeUninit<u8>]`, tracking how much of it has
+/// been filled with data so far.
+pub struct ReadBuffer<'a> {
+    buf: &'a mut [MaybeUninit<u8>],
+    filled: usize,
+}
+
+impl<'a> ReadBuffer<'a> {
+    /// Wraps `buf` with nothing yet filled.
+    pub fn new(buf: &'a mut [MaybeUninit<u8>]) -> ReadBuffer<'a> {
+        ReadBuffer{
+            buf: buf,
+            filled: 0,
+        }
+    }
+
+    /// Total capacity of the underlying storage.
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Number of bytes filled so far.
+    pub fn len(&self) -> usize {
+        self.filled
+    }
+
+    /// Whether no bytes have been filled yet.
+    pub fn is_empty(&self) -> bool {
+        self.filled == 0
+    }
+
+    /// The filled portion of the buffer, safe to read as initialized
+    /// bytes.
+    pub fn filled(&self) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(self.buf.as_ptr() as *const u8, self.filled)
+        }
+    }
+
+    /// The unfilled, possibly-uninitialized tail. Safe to hand directly
+    /// to a syscall like `read(2)`/`ReadFile`, which writes into it
+    /// without needing it to be initialized first. Call `advance` with
+    /// however many bytes the syscall actually wrote.
+    pub fn unfilled(&mut self) -> &mut [MaybeUninit<u8>] {
+        &mut self.buf[self.filled..]
+    }
+
+    /// Marks `n` more bytes, written into the region last returned by
+    /// `unfilled`, as filled.
+    pub fn advance(&mut self, n: usize) {
+        let new_filled = self.filled + n;
+        assert!(new_filled <= self.buf.len(), "advance past buffer capacity");
+
+        self.filled = new_filled;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::mem::MaybeUninit;
+
+    use super::ReadBuffer;
+
+    fn storage(cap: usize) -> Vec<MaybeUninit<u8>> {
+        (0..cap).map(|_| MaybeUninit::uninit()).collect()
+    }
+
+    #[test]
+    fn starts_empty() {
+        let mut storage = storage(8);
+        let buf = ReadBuffer::new(&mut storage);
+
+        assert_eq!(buf.capacity(), 8);
+        assert_eq!(buf.len(), 0);
+        assert!(buf.is_empty());
+        assert_eq!(buf.filled(), &[] as &[u8]);
+    }
+
+    #[test]
+    fn unfilled_spans_the_whole_buffer_before_any_advance() {
+        let mut storage = storage(4);
+        let mut buf = ReadBuffer::new(&mut storage);
+
+        assert_eq!(buf.unfilled().len(), 4);
+    }
+
+    #[test]
+    fn advance_grows_filled_and_shrinks_unfilled() {
+        let mut storage = storage(4);
+        let mut buf = ReadBuffer::new(&mut storage);
+
+        for (i, slot) in buf.unfilled().iter_mut().enumerate() {
+            *slot = MaybeUninit::new(i as u8);
+        }
+        buf.advance(2);
+
+        assert_eq!(buf.filled(), &[0, 1]);
+        assert_eq!(buf.unfilled().len(), 2);
+    }
+
+    #[test]
+    fn successive_reads_accumulate_into_filled() {
+        let mut storage = storage(4);
+        let mut buf = ReadBuffer::new(&mut storage);
+
+        buf.unfilled()[0] = MaybeUninit::new(b'h');
+        buf.advance(1);
+        buf.unfilled()[0] = MaybeUninit::new(b'i');
+        buf.advance(1);
+
+        assert_eq!(buf.filled(), b"hi");
+    }
+
+    #[test]
+    #[should_panic]
+    fn advance_past_capacity_panics() {
+        let mut storage = storage(2);
+        let mut buf = ReadBuffer::new(&mut storage);
+
+        buf.advance(3);
+    }
+}
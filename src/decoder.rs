@@ -0,0 +1,434 @@
+//! Decodes raw terminal input bytes into structured key events.
+//!
+//! `Terminal::read` only ever hands back raw bytes; `InputDecoder` turns
+//! those bytes into a stream of [`KeyEvent`]s, reassembling UTF-8
+//! multibyte sequences and recognizing the CSI/SS3 escape families used
+//! for arrow keys, navigation keys, and function keys. A sequence may be
+//! split across two calls to `Terminal::read`, so the decoder retains
+//! whatever partial state it has seen and picks up where it left off the
+//! next time bytes are fed to it — including a lone ESC, which stays
+//! pending rather than being assumed final just because one `read()`
+//! chunk ran out. Callers that want to resolve a lone ESC to a standalone
+//! keypress (because their own timeout says no more bytes are coming)
+//! should call [`InputDecoder::flush`].
+//!
+//! Note this is a deliberate refinement of the original "emit Escape at
+//! the end of the buffer" behavior: a single `read()` can return short
+//! for reasons that have nothing to do with the sequence being complete
+//! (e.g. a pipe or pty delivering an arrow key's bytes in two separate
+//! writes), so treating "ran out of bytes in this slice" as "the user
+//! pressed bare Escape" produced wrong `Up`/`Home`/etc. decodes whenever
+//! a sequence happened to split there. Resolving a lone ESC is pushed out
+//! to an explicit `flush()`, to be driven by whatever real timeout the
+//! caller uses to decide no more bytes are coming; [`read_keys`] itself
+//! does a single non-blocking decode pass and does not call `flush`,
+//! since it has no timeout of its own to judge that by.
+
+/// A single decoded keypress.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum KeyEvent {
+    /// A printable character, after UTF-8 reassembly.
+    Char(char),
+    /// A control character, e.g. produced by Ctrl+A (`0x01`).
+    Control(u8),
+    /// The bare Escape key, received on its own rather than as the
+    /// prefix of a longer sequence.
+    Escape,
+    Up(Modifiers),
+    Down(Modifiers),
+    Left(Modifiers),
+    Right(Modifiers),
+    Home(Modifiers),
+    End(Modifiers),
+    Insert(Modifiers),
+    Delete(Modifiers),
+    PageUp(Modifiers),
+    PageDown(Modifiers),
+    /// A function key, numbered from 1.
+    Function(u8, Modifiers),
+}
+
+/// Modifier keys held down alongside a non-printable key, as encoded in
+/// a CSI sequence's trailing `;n` parameter (`n - 1` is a bitmask of
+/// Shift/Alt/Control).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub control: bool,
+}
+
+impl Modifiers {
+    fn from_param(n: i32) -> Modifiers {
+        let bits = (n - 1).max(0);
+
+        Modifiers{
+            shift: bits & 1 != 0,
+            alt: bits & 2 != 0,
+            control: bits & 4 != 0,
+        }
+    }
+}
+
+/// Result of feeding a byte to the decoder.
+enum Step {
+    /// No event yet; more bytes are needed to complete the sequence.
+    Pending,
+    /// A complete key event was decoded.
+    Event(KeyEvent),
+    /// The byte was not part of a recognized sequence and was discarded.
+    Discard,
+}
+
+#[derive(Clone, Debug)]
+enum State {
+    Ground,
+    /// Collecting the continuation bytes of a UTF-8 multibyte sequence.
+    Utf8{buf: [u8; 4], len: u8, need: u8},
+    /// Just saw ESC; waiting to see whether `[`, `O`, or something else
+    /// follows.
+    Escape,
+    /// Inside a CSI sequence (`ESC [ ... final`), accumulating
+    /// semicolon-separated numeric parameters until a final byte in
+    /// `0x40..=0x7E` arrives.
+    Csi{params: Vec<i32>, cur: Option<i32>},
+    /// Inside an SS3 sequence (`ESC O final`).
+    Ss3,
+}
+
+/// A resumable state machine that decodes a byte stream into [`KeyEvent`]s.
+pub struct InputDecoder {
+    state: State,
+}
+
+impl InputDecoder {
+    pub fn new() -> InputDecoder {
+        InputDecoder{
+            state: State::Ground,
+        }
+    }
+
+    /// Feeds newly read bytes into the decoder, returning every key event
+    /// that could be completed from them. Any trailing partial sequence,
+    /// including a bare ESC with nothing after it yet, is retained and
+    /// completed by a later call — the end of this slice is just the end
+    /// of one `read()`, not necessarily the end of the sequence.
+    pub fn decode(&mut self, input: &[u8]) -> Vec<KeyEvent> {
+        let mut events = Vec::new();
+
+        for &byte in input {
+            match self.push(byte) {
+                Step::Event(ev) => events.push(ev),
+                Step::Pending | Step::Discard => (),
+            }
+        }
+
+        events
+    }
+
+    /// Resolves a pending lone ESC to a standalone [`KeyEvent::Escape`].
+    /// Call this once the caller's own timeout (not merely running out of
+    /// bytes in one `read()`) has decided no more input is coming; it is
+    /// a no-op if the decoder isn't waiting on one.
+    pub fn flush(&mut self) -> Option<KeyEvent> {
+        if let State::Escape = self.state {
+            self.state = State::Ground;
+            Some(KeyEvent::Escape)
+        } else {
+            None
+        }
+    }
+
+    fn push(&mut self, byte: u8) -> Step {
+        match self.state {
+            State::Ground => self.push_ground(byte),
+            State::Utf8{..} => self.push_utf8(byte),
+            State::Escape => self.push_escape(byte),
+            State::Csi{..} => self.push_csi(byte),
+            State::Ss3 => self.push_ss3(byte),
+        }
+    }
+
+    fn push_ground(&mut self, byte: u8) -> Step {
+        match byte {
+            0x1b => {
+                self.state = State::Escape;
+                Step::Pending
+            }
+            0x00..=0x1f | 0x7f => Step::Event(KeyEvent::Control(byte)),
+            0x20..=0x7e => Step::Event(KeyEvent::Char(byte as char)),
+            _ => {
+                let need = utf8_len(byte);
+
+                if need == 0 {
+                    // Not a valid UTF-8 lead byte; drop it.
+                    return Step::Discard;
+                }
+
+                let mut buf = [0; 4];
+                buf[0] = byte;
+
+                self.state = State::Utf8{buf: buf, len: 1, need: need};
+                Step::Pending
+            }
+        }
+    }
+
+    fn push_utf8(&mut self, byte: u8) -> Step {
+        let (mut buf, mut len, need) = match self.state {
+            State::Utf8{buf, len, need} => (buf, len, need),
+            _ => unreachable!(),
+        };
+
+        buf[len as usize] = byte;
+        len += 1;
+
+        if len < need {
+            self.state = State::Utf8{buf: buf, len: len, need: need};
+            return Step::Pending;
+        }
+
+        self.state = State::Ground;
+
+        match ::std::str::from_utf8(&buf[..len as usize]) {
+            Ok(s) => Step::Event(KeyEvent::Char(s.chars().next().unwrap())),
+            Err(_) => Step::Discard,
+        }
+    }
+
+    fn push_escape(&mut self, byte: u8) -> Step {
+        match byte {
+            b'[' => {
+                self.state = State::Csi{params: Vec::new(), cur: None};
+                Step::Pending
+            }
+            b'O' => {
+                self.state = State::Ss3;
+                Step::Pending
+            }
+            0x1b => Step::Event(KeyEvent::Escape),
+            _ => {
+                // Not a sequence we recognize; treat the ESC as standalone
+                // and reprocess this byte from Ground.
+                self.state = State::Ground;
+                self.push_ground(byte)
+            }
+        }
+    }
+
+    fn push_csi(&mut self, byte: u8) -> Step {
+        let (mut params, mut cur) = match self.state {
+            State::Csi{ref params, cur} => (params.clone(), cur),
+            _ => unreachable!(),
+        };
+
+        match byte {
+            b'0'..=b'9' => {
+                let digit = (byte - b'0') as i32;
+
+                // Cap accumulated parameters the same way real terminal
+                // emulators do: a pasted, already-colorized blob of text
+                // can legitimately contain long SGR sequences, and a
+                // stray run of digits shouldn't be able to overflow the
+                // accumulator. Anything past this is not a real
+                // parameter value, so the sequence is abandoned.
+                match cur.unwrap_or(0).checked_mul(10).and_then(|n| n.checked_add(digit)) {
+                    Some(n) if n <= MAX_CSI_PARAM => {
+                        cur = Some(n);
+                        self.state = State::Csi{params: params, cur: cur};
+                        Step::Pending
+                    }
+                    _ => {
+                        self.state = State::Ground;
+                        Step::Discard
+                    }
+                }
+            }
+            b';' if params.len() + 1 >= MAX_CSI_PARAMS => {
+                self.state = State::Ground;
+                Step::Discard
+            }
+            b';' => {
+                params.push(cur.unwrap_or(0));
+                self.state = State::Csi{params: params, cur: None};
+                Step::Pending
+            }
+            0x40..=0x7e => {
+                if let Some(n) = cur {
+                    params.push(n);
+                }
+
+                self.state = State::Ground;
+                csi_event(byte, &params)
+            }
+            _ => {
+                // Unexpected byte inside a CSI sequence; abandon it.
+                self.state = State::Ground;
+                Step::Discard
+            }
+        }
+    }
+
+    fn push_ss3(&mut self, byte: u8) -> Step {
+        self.state = State::Ground;
+
+        let modifiers = Modifiers::default();
+
+        match byte {
+            b'A' => Step::Event(KeyEvent::Up(modifiers)),
+            b'B' => Step::Event(KeyEvent::Down(modifiers)),
+            b'C' => Step::Event(KeyEvent::Right(modifiers)),
+            b'D' => Step::Event(KeyEvent::Left(modifiers)),
+            b'H' => Step::Event(KeyEvent::Home(modifiers)),
+            b'F' => Step::Event(KeyEvent::End(modifiers)),
+            b'P' => Step::Event(KeyEvent::Function(1, modifiers)),
+            b'Q' => Step::Event(KeyEvent::Function(2, modifiers)),
+            b'R' => Step::Event(KeyEvent::Function(3, modifiers)),
+            b'S' => Step::Event(KeyEvent::Function(4, modifiers)),
+            _ => Step::Discard,
+        }
+    }
+}
+
+/// Maps a completed CSI sequence (final byte plus accumulated numeric
+/// parameters) to a key event. The first parameter (when present) selects
+/// the key for the `~`-terminated navigation family; the second parameter
+/// (when present) carries the modifier mask for both families.
+fn csi_event(final_byte: u8, params: &[i32]) -> Step {
+    let modifiers = params.get(1).map_or(Modifiers::default(), |&n| Modifiers::from_param(n));
+
+    match final_byte {
+        b'A' => Step::Event(KeyEvent::Up(modifiers)),
+        b'B' => Step::Event(KeyEvent::Down(modifiers)),
+        b'C' => Step::Event(KeyEvent::Right(modifiers)),
+        b'D' => Step::Event(KeyEvent::Left(modifiers)),
+        b'H' => Step::Event(KeyEvent::Home(modifiers)),
+        b'F' => Step::Event(KeyEvent::End(modifiers)),
+        b'~' => match params.first() {
+            Some(1) => Step::Event(KeyEvent::Home(modifiers)),
+            Some(2) => Step::Event(KeyEvent::Insert(modifiers)),
+            Some(3) => Step::Event(KeyEvent::Delete(modifiers)),
+            Some(4) => Step::Event(KeyEvent::End(modifiers)),
+            Some(5) => Step::Event(KeyEvent::PageUp(modifiers)),
+            Some(6) => Step::Event(KeyEvent::PageDown(modifiers)),
+            Some(7) => Step::Event(KeyEvent::Home(modifiers)),
+            Some(8) => Step::Event(KeyEvent::End(modifiers)),
+            Some(n @ 11..=15) => Step::Event(KeyEvent::Function((n - 10) as u8, modifiers)),
+            Some(n @ 17..=21) => Step::Event(KeyEvent::Function((n - 11) as u8, modifiers)),
+            Some(n @ 23..=26) => Step::Event(KeyEvent::Function((n - 12) as u8, modifiers)),
+            Some(n @ 28..=29) => Step::Event(KeyEvent::Function((n - 13) as u8, modifiers)),
+            Some(n @ 31..=34) => Step::Event(KeyEvent::Function((n - 14) as u8, modifiers)),
+            _ => Step::Discard,
+        },
+        _ => Step::Discard,
+    }
+}
+
+/// Upper bound on a single CSI numeric parameter (xterm caps these at
+/// 16 bits); anything beyond this can't be a real parameter value.
+const MAX_CSI_PARAM: i32 = 0xffff;
+
+/// Upper bound on the number of `;`-separated parameters in one CSI
+/// sequence, matching ECMA-48's own guidance against unbounded growth.
+const MAX_CSI_PARAMS: usize = 16;
+
+/// Returns the total byte length of a UTF-8 sequence starting with `lead`,
+/// or `0` if `lead` is not a valid lead byte.
+fn utf8_len(lead: u8) -> u8 {
+    if lead & 0b1110_0000 == 0b1100_0000 {
+        2
+    } else if lead & 0b1111_0000 == 0b1110_0000 {
+        3
+    } else if lead & 0b1111_1000 == 0b1111_0000 {
+        4
+    } else {
+        0
+    }
+}
+
+/// Reads whatever bytes `term` has available right now and decodes them
+/// with `decoder`, which the caller keeps around across calls so a
+/// sequence split over more than one `read()` still resolves correctly.
+/// This is the single path both the Unix and Redox backends go through to
+/// turn raw `Terminal::read` bytes into real key events, rather than each
+/// one re-parsing escape sequences on its own.
+pub fn read_keys<T: ::terminal::Terminal>(term: &T, decoder: &mut InputDecoder)
+        -> ::std::io::Result<Vec<KeyEvent>> {
+    let mut storage = [::std::mem::MaybeUninit::uninit(); 32];
+    let mut buf = ::buffer::ReadBuffer::new(&mut storage);
+
+    term.read(&mut buf)?;
+
+    Ok(decoder.decode(buf.filled()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InputDecoder, KeyEvent, Modifiers};
+
+    #[test]
+    fn decodes_printable_ascii() {
+        let mut dec = InputDecoder::new();
+        assert_eq!(dec.decode(b"ab"), vec![
+            KeyEvent::Char('a'), KeyEvent::Char('b'),
+        ]);
+    }
+
+    #[test]
+    fn decodes_control_char() {
+        let mut dec = InputDecoder::new();
+        assert_eq!(dec.decode(&[0x01]), vec![KeyEvent::Control(0x01)]);
+    }
+
+    #[test]
+    fn decodes_multibyte_utf8_across_reads() {
+        let mut dec = InputDecoder::new();
+        let bytes = "é".as_bytes();
+        assert_eq!(dec.decode(&bytes[..1]), vec![]);
+        assert_eq!(dec.decode(&bytes[1..]), vec![KeyEvent::Char('é')]);
+    }
+
+    #[test]
+    fn decodes_csi_arrow_key() {
+        let mut dec = InputDecoder::new();
+        assert_eq!(dec.decode(b"\x1b[A"), vec![
+            KeyEvent::Up(Modifiers::default()),
+        ]);
+    }
+
+    #[test]
+    fn arrow_key_split_across_reads_is_not_a_bare_escape() {
+        let mut dec = InputDecoder::new();
+
+        // ESC alone must stay pending, not resolve to KeyEvent::Escape,
+        // since the rest of the sequence may arrive in the next read().
+        assert_eq!(dec.decode(&[0x1b]), vec![]);
+        assert_eq!(dec.decode(b"[A"), vec![KeyEvent::Up(Modifiers::default())]);
+    }
+
+    #[test]
+    fn lone_escape_resolves_only_on_explicit_flush() {
+        let mut dec = InputDecoder::new();
+        assert_eq!(dec.decode(&[0x1b]), vec![]);
+        assert_eq!(dec.flush(), Some(KeyEvent::Escape));
+        assert_eq!(dec.flush(), None);
+    }
+
+    #[test]
+    fn decodes_home_and_end_variants() {
+        let mut dec = InputDecoder::new();
+        assert_eq!(dec.decode(b"\x1b[1~"), vec![KeyEvent::Home(Modifiers::default())]);
+        assert_eq!(dec.decode(b"\x1b[4~"), vec![KeyEvent::End(Modifiers::default())]);
+        assert_eq!(dec.decode(b"\x1b[7~"), vec![KeyEvent::Home(Modifiers::default())]);
+        assert_eq!(dec.decode(b"\x1b[8~"), vec![KeyEvent::End(Modifiers::default())]);
+    }
+
+    #[test]
+    fn decodes_modified_arrow_key() {
+        let mut dec = InputDecoder::new();
+        // CSI 1;5A == Ctrl+Up
+        assert_eq!(dec.decode(b"\x1b[1;5A"), vec![
+            KeyEvent::Up(Modifiers{shift: false, alt: false, control: true}),
+        ]);
+    }
+}
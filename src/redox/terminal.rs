@@ -0,0 +1,421 @@
+use std::cell::RefCell;
+use std::env::var;
+use std::ffi::CStr;
+use std::io::{self, stdout, stderr, Write};
+use std::mem::MaybeUninit;
+use std::time::{Duration, Instant};
+
+use redox_termios::{self, Termios};
+use syscall;
+
+use buffer::ReadBuffer;
+use decoder::{self, InputDecoder, KeyEvent};
+use sys::terminfo::{setup_term, get_str, put, term_param};
+use terminal::{CursorMode, Signal, SignalSet, Size, Terminal};
+
+/// The controlling terminal's own stdin/stdout file descriptors, whose
+/// `termios:`/`winsize:` views we `dup` into below. The termios/winsize
+/// scheme is a property of a specific tty instance, not a singleton path,
+/// so it must be reached via the real fd rather than a bare scheme name.
+const STDIN_FILENO: usize = 0;
+const STDOUT_FILENO: usize = 1;
+
+pub struct RedoxTerminal {
+    /// Terminal name
+    name: Option<String>,
+
+    /// End-of-file character
+    eof: u8,
+    /// Literal next character
+    literal: u8,
+    /// Erase/backspace character
+    erase: u8,
+    /// Word erase character
+    word_erase: u8,
+    /// Kill character
+    kill: u8,
+
+    key_delete: &'static CStr,
+    key_insert: &'static CStr,
+
+    clear: &'static CStr,
+    clear_eos: &'static CStr,
+    cursor_up: &'static CStr,
+    cursor_up_n: &'static CStr,
+    cursor_down_n: &'static CStr,
+    cursor_left: &'static CStr,
+    cursor_left_n: &'static CStr,
+    cursor_right: &'static CStr,
+    cursor_right_n: &'static CStr,
+
+    /// Decodes bytes from `read` into key events; kept here so a
+    /// sequence split across more than one `read` call still resolves.
+    input_decoder: RefCell<InputDecoder>,
+}
+
+#[must_use]
+pub struct TerminalGuard {
+    old_tio: Termios,
+}
+
+impl TerminalGuard {
+    fn new(old_tio: Termios) -> TerminalGuard {
+        TerminalGuard{
+            old_tio: old_tio,
+        }
+    }
+
+    fn restore(&self) -> io::Result<()> {
+        set_termios(&self.old_tio)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.restore() {
+            let _ = writeln!(stderr(), "failed to restore terminal: {}", e);
+        }
+    }
+}
+
+impl Terminal for RedoxTerminal {
+    type PrepareGuard = TerminalGuard;
+
+    fn new() -> io::Result<RedoxTerminal> {
+        let tio = get_termios()?;
+
+        setup_term()?;
+
+        Ok(RedoxTerminal{
+            name: var("TERM").ok(),
+
+            eof: tio.c_cc[redox_termios::VEOF],
+            literal: tio.c_cc[redox_termios::VLNEXT],
+            erase: tio.c_cc[redox_termios::VERASE],
+            word_erase: tio.c_cc[redox_termios::VWERASE],
+            kill: tio.c_cc[redox_termios::VKILL],
+
+            key_delete: get_str("kdch1")?,
+            key_insert: get_str("kich1")?,
+
+            clear: get_str("clear")?,
+            clear_eos: get_str("ed")?,
+            cursor_up: get_str("cuu1")?,
+            cursor_up_n: get_str("cuu")?,
+            cursor_down_n: get_str("cud")?,
+            cursor_left: get_str("cub1")?,
+            cursor_left_n: get_str("cub")?,
+            cursor_right: get_str("cuf1")?,
+            cursor_right_n: get_str("cuf")?,
+
+            input_decoder: RefCell::new(InputDecoder::new()),
+        })
+    }
+
+    fn eof_char(&self) -> char { self.eof as char }
+    fn literal_char(&self) -> char { self.literal as char }
+    fn erase_char(&self) -> char { self.erase as char }
+    fn word_erase_char(&self) -> char { self.word_erase as char }
+    fn kill_char(&self) -> char { self.kill as char }
+
+    fn delete_seq(&self) -> &str {
+        self.key_delete.to_str().unwrap()
+    }
+
+    fn insert_seq(&self) -> &str {
+        self.key_insert.to_str().unwrap()
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(|s| &s[..])
+    }
+
+    fn size(&self) -> io::Result<Size> {
+        let sz = get_winsize()?;
+
+        Ok(Size{
+            lines: sz.ws_row as usize,
+            columns: sz.ws_col as usize,
+        })
+    }
+
+    fn clear_screen(&self) -> io::Result<()> {
+        put(&self.clear)
+    }
+
+    fn clear_to_screen_end(&self) -> io::Result<()> {
+        put(&self.clear_eos)
+    }
+
+    fn move_up(&self, n: usize) -> io::Result<()> {
+        if n == 0 {
+            Ok(())
+        } else if n == 1 {
+            put(&self.cursor_up)
+        } else {
+            let s = term_param(&self.cursor_up_n, n as i32)?;
+            put(&s)
+        }
+    }
+
+    fn move_down(&self, n: usize) -> io::Result<()> {
+        if n == 0 {
+            Ok(())
+        } else {
+            // Same caveat as the Unix backend: cud1 behaves like '\n',
+            // so we always go through parm_down_cursor (cud).
+            let s = term_param(&self.cursor_down_n, n as i32)?;
+            put(&s)
+        }
+    }
+
+    fn move_left(&self, n: usize) -> io::Result<()> {
+        if n == 0 {
+            Ok(())
+        } else if n == 1 {
+            put(&self.cursor_left)
+        } else {
+            let s = term_param(&self.cursor_left_n, n as i32)?;
+            put(&s)
+        }
+    }
+
+    fn move_right(&self, n: usize) -> io::Result<()> {
+        if n == 0 {
+            Ok(())
+        } else if n == 1 {
+            put(&self.cursor_right)
+        } else {
+            let s = term_param(&self.cursor_right_n, n as i32)?;
+            put(&s)
+        }
+    }
+
+    fn move_to_first_col(&self) -> io::Result<()> {
+        self.write("\r")
+    }
+
+    fn set_cursor_mode(&self, _mode: CursorMode) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn wait_for_input(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        // Redox has no select(2) equivalent on the scheme used here, so we
+        // fall back to polling `input_ready` with a short sleep between attempts.
+        // This is coarser than the Unix backend, but keeps behavior correct
+        // in the absence of SIGWINCH/SIGCONT-style wakeups.
+        let start = Instant::now();
+
+        loop {
+            if input_ready()? {
+                return Ok(true);
+            }
+
+            if let Some(timeout) = timeout {
+                if start.elapsed() >= timeout {
+                    return Ok(false);
+                }
+            }
+
+            ::std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn prepare(&self, catch_signals: bool, report_signals: SignalSet)
+            -> io::Result<TerminalGuard> {
+        let old_tio = get_termios()?;
+        let mut tio = old_tio;
+
+        tio.c_iflag &= !(redox_termios::INLCR | redox_termios::ICRNL);
+        tio.c_lflag &= !(redox_termios::ICANON | redox_termios::ECHO);
+        tio.c_cc[redox_termios::VMIN] = 0;
+        tio.c_cc[redox_termios::VTIME] = 0;
+
+        set_termios(&tio)?;
+
+        let guard = TerminalGuard::new(old_tio);
+
+        // Redox has no SIGCONT/SIGINT/SIGTSTP/SIGQUIT delivery for the
+        // controlling terminal, so `catch_signals`/`report_signals` are
+        // accepted but have nothing to install; callers simply never see
+        // those signals reported back to them here.
+        let _ = catch_signals;
+        let _ = report_signals;
+
+        Ok(guard)
+    }
+
+    fn get_signal(&self) -> Option<Signal> {
+        None
+    }
+
+    fn take_signal(&self) -> Option<Signal> {
+        None
+    }
+
+    fn read_signals(&self) -> io::Result<TerminalGuard> {
+        let old_tio = get_termios()?;
+        Ok(TerminalGuard::new(old_tio))
+    }
+
+    fn read(&self, buf: &mut ReadBuffer) -> io::Result<usize> {
+        let n = read_stdin(buf.unfilled())?;
+        buf.advance(n);
+        Ok(n)
+    }
+
+    fn write(&self, s: &str) -> io::Result<()> {
+        let stdout = stdout();
+        let mut lock = stdout.lock();
+
+        lock.write_all(s.as_bytes())?;
+        lock.flush()
+    }
+}
+
+impl RedoxTerminal {
+    /// Reads more bytes from the terminal and decodes them into key
+    /// events, resuming any escape sequence the previous call left
+    /// partway through.
+    pub fn read_keys(&self) -> io::Result<Vec<KeyEvent>> {
+        decoder::read_keys(self, &mut self.input_decoder.borrow_mut())
+    }
+}
+
+fn from_syscall_error(e: syscall::Error) -> io::Error {
+    io::Error::from_raw_os_error(e.errno as i32)
+}
+
+fn get_termios() -> io::Result<Termios> {
+    let fd = syscall::dup(STDIN_FILENO, b"termios")
+        .map_err(from_syscall_error)?;
+
+    let mut termios = Termios::default();
+
+    // `syscall::read` takes a byte slice, not a `Termios` by reference.
+    let res = syscall::read(fd, as_bytes_mut(&mut termios))
+        .map_err(from_syscall_error);
+    let _ = syscall::close(fd);
+
+    res?;
+
+    Ok(termios)
+}
+
+fn set_termios(tio: &Termios) -> io::Result<()> {
+    let fd = syscall::dup(STDIN_FILENO, b"termios")
+        .map_err(from_syscall_error)?;
+
+    let res = syscall::write(fd, as_bytes(tio))
+        .map_err(from_syscall_error);
+    let _ = syscall::close(fd);
+
+    res?;
+
+    Ok(())
+}
+
+#[repr(C)]
+struct Winsize {
+    ws_row: u16,
+    ws_col: u16,
+    ws_xpixel: u16,
+    ws_ypixel: u16,
+}
+
+fn get_winsize() -> io::Result<Winsize> {
+    let fd = syscall::dup(STDOUT_FILENO, b"winsize")
+        .map_err(from_syscall_error)?;
+
+    let mut sz = [0u8; 4 * 2];
+
+    let res = syscall::read(fd, &mut sz)
+        .map_err(from_syscall_error);
+    let _ = syscall::close(fd);
+
+    res?;
+
+    Ok(Winsize{
+        ws_row: u16::from(sz[0]) | (u16::from(sz[1]) << 8),
+        ws_col: u16::from(sz[2]) | (u16::from(sz[3]) << 8),
+        ws_xpixel: u16::from(sz[4]) | (u16::from(sz[5]) << 8),
+        ws_ypixel: u16::from(sz[6]) | (u16::from(sz[7]) << 8),
+    })
+}
+
+/// Readiness bit set on an `Event` when the registered file has data
+/// waiting to be read.
+const EVENT_READ: usize = syscall::flag::EVENT_READ.bits();
+
+/// Mirrors `syscall::data::Event`'s layout so we can register/read it as
+/// raw bytes without depending on the exact re-export path.
+#[repr(C)]
+struct Event {
+    id: usize,
+    flags: usize,
+    data: usize,
+}
+
+fn input_ready() -> io::Result<bool> {
+    // Registering fd 0 with the event scheme and reading back whether it
+    // fired tells us stdin is readable without taking any bytes out of
+    // it — unlike reading through a `dup`'d fd, which shares stdin's
+    // position and would consume the very byte we're trying to peek at.
+    let event_fd = syscall::open("event:", syscall::O_RDWR | syscall::O_NONBLOCK)
+        .map_err(from_syscall_error)?;
+
+    let register = Event{id: 0, flags: EVENT_READ, data: 0};
+    let register_res = syscall::write(event_fd, as_bytes(&register))
+        .map_err(from_syscall_error);
+
+    if let Err(e) = register_res {
+        let _ = syscall::close(event_fd);
+        return Err(e);
+    }
+
+    let mut fired = Event{id: 0, flags: 0, data: 0};
+    let res = syscall::read(event_fd, as_bytes_mut(&mut fired));
+    let _ = syscall::close(event_fd);
+
+    match res {
+        Ok(0) => Ok(false),
+        Ok(_) => Ok(fired.flags & EVENT_READ != 0),
+        Err(ref e) if e.errno == syscall::EAGAIN => Ok(false),
+        Err(e) => Err(from_syscall_error(e)),
+    }
+}
+
+fn as_bytes<T>(v: &T) -> &[u8] {
+    unsafe {
+        ::std::slice::from_raw_parts(v as *const T as *const u8, ::std::mem::size_of::<T>())
+    }
+}
+
+fn as_bytes_mut<T>(v: &mut T) -> &mut [u8] {
+    unsafe {
+        ::std::slice::from_raw_parts_mut(v as *mut T as *mut u8, ::std::mem::size_of::<T>())
+    }
+}
+
+fn read_stdin(buf: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+    retry(|| {
+        // `syscall::read` writes into this region without
+        // requiring it to be initialized first, so the raw cast is sound.
+        let buf = unsafe {
+            ::std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, buf.len())
+        };
+
+        syscall::read(0, buf).map_err(from_syscall_error)
+    })
+}
+
+// Retries a closure when the error kind is Interrupted
+fn retry<F>(mut f: F) -> io::Result<usize>
+        where F: FnMut() -> io::Result<usize> {
+    loop {
+        match f() {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            res => return res
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! The `Terminal` trait and the platform-independent types it trades in.
+//!
+//! Each supported platform implements `Terminal` once
+//! (`unix::terminal::UnixTerminal`, `redox::terminal::RedoxTerminal`, ...);
+//! everything else in the crate talks to a terminal only through this
+//! trait.
+
+use std::io;
+use std::time::Duration;
+
+use buffer::ReadBuffer;
+
+/// A terminal's current dimensions, in character cells.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Size {
+    pub lines: usize,
+    pub columns: usize,
+}
+
+/// Selects how the terminal cursor is drawn.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CursorMode {
+    Normal,
+    Overwrite,
+}
+
+/// A signal that may be reported back to a caller blocked in
+/// `Terminal::wait_for_input`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Signal {
+    /// The process was resumed after being suspended (`SIGCONT`).
+    Continue,
+    /// The user requested an interrupt (`SIGINT`).
+    Interrupt,
+    /// The user requested the process suspend (`SIGTSTP`).
+    Suspend,
+    /// The user requested the process quit (`SIGQUIT`).
+    Quit,
+    /// The terminal window was resized (`SIGWINCH`).
+    Resize,
+}
+
+/// A set of `Signal`s a caller wants `Terminal::prepare` to catch and
+/// report.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SignalSet {
+    bits: u8,
+}
+
+impl SignalSet {
+    /// An empty set.
+    pub fn new() -> SignalSet {
+        SignalSet{bits: 0}
+    }
+
+    /// A set containing every `Signal`.
+    pub fn all() -> SignalSet {
+        let mut set = SignalSet::new();
+
+        set.insert(Signal::Continue);
+        set.insert(Signal::Interrupt);
+        set.insert(Signal::Suspend);
+        set.insert(Signal::Quit);
+        set.insert(Signal::Resize);
+
+        set
+    }
+
+    pub fn insert(&mut self, signal: Signal) {
+        self.bits |= SignalSet::bit(signal);
+    }
+
+    pub fn contains(&self, signal: Signal) -> bool {
+        self.bits & SignalSet::bit(signal) != 0
+    }
+
+    fn bit(signal: Signal) -> u8 {
+        match signal {
+            Signal::Continue => 1 << 0,
+            Signal::Interrupt => 1 << 1,
+            Signal::Suspend => 1 << 2,
+            Signal::Quit => 1 << 3,
+            Signal::Resize => 1 << 4,
+        }
+    }
+}
+
+/// A platform-specific terminal backend.
+pub trait Terminal: Sized {
+    /// RAII guard returned by `prepare`/`read_signals` that restores the
+    /// terminal's previous state when dropped.
+    type PrepareGuard;
+
+    fn new() -> io::Result<Self>;
+
+    fn eof_char(&self) -> char;
+    fn literal_char(&self) -> char;
+    fn erase_char(&self) -> char;
+    fn word_erase_char(&self) -> char;
+    fn kill_char(&self) -> char;
+
+    fn delete_seq(&self) -> &str;
+    fn insert_seq(&self) -> &str;
+
+    fn name(&self) -> Option<&str>;
+
+    fn size(&self) -> io::Result<Size>;
+
+    fn clear_screen(&self) -> io::Result<()>;
+    fn clear_to_screen_end(&self) -> io::Result<()>;
+
+    fn move_up(&self, n: usize) -> io::Result<()>;
+    fn move_down(&self, n: usize) -> io::Result<()>;
+    fn move_left(&self, n: usize) -> io::Result<()>;
+    fn move_right(&self, n: usize) -> io::Result<()>;
+    fn move_to_first_col(&self) -> io::Result<()>;
+
+    fn set_cursor_mode(&self, mode: CursorMode) -> io::Result<()>;
+
+    fn wait_for_input(&self, timeout: Option<Duration>) -> io::Result<bool>;
+
+    fn prepare(&self, catch_signals: bool, report_signals: SignalSet)
+        -> io::Result<Self::PrepareGuard>;
+
+    fn get_signal(&self) -> Option<Signal>;
+    fn take_signal(&self) -> Option<Signal>;
+
+    fn read_signals(&self) -> io::Result<Self::PrepareGuard>;
+
+    fn read(&self, buf: &mut ReadBuffer) -> io::Result<usize>;
+
+    fn write(&self, s: &str) -> io::Result<()>;
+}
@@ -1,8 +1,8 @@
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::env::var;
 use std::ffi::CStr;
 use std::io::{self, stdout, stderr, Write};
-use std::mem::{forget, zeroed};
+use std::mem::{forget, zeroed, MaybeUninit};
 use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
 use std::time::Duration;
 
@@ -27,6 +27,8 @@ use nix::sys::termios::{
 };
 use nix::sys::time::TimeVal;
 
+use buffer::ReadBuffer;
+use decoder::{self, InputDecoder, KeyEvent};
 use sys::terminfo::{setup_term, get_str, put, term_param};
 use terminal::{CursorMode, Signal, SignalSet, Size, Terminal};
 
@@ -61,6 +63,10 @@ pub struct UnixTerminal {
     /// If SIGCONT is received,
     /// resume prepared terminal session using these parameters.
     resume: Cell<Option<(bool, SignalSet)>>,
+
+    /// Decodes bytes from `read` into key events; kept here so a
+    /// sequence split across more than one `read` call still resolves.
+    input_decoder: RefCell<InputDecoder>,
 }
 
 #[must_use]
@@ -70,6 +76,7 @@ pub struct TerminalGuard {
     old_sigint: Option<SigAction>,
     old_sigtstp: Option<SigAction>,
     old_sigquit: Option<SigAction>,
+    old_sigwinch: Option<SigAction>,
 }
 
 impl TerminalGuard {
@@ -80,6 +87,7 @@ impl TerminalGuard {
             old_sigint: None,
             old_sigtstp: None,
             old_sigquit: None,
+            old_sigwinch: None,
         }
     }
 
@@ -98,6 +106,9 @@ impl TerminalGuard {
         if let Some(ref old_sigquit) = self.old_sigquit {
             unsafe { sigaction(NixSignal::SIGQUIT, old_sigquit)?; }
         }
+        if let Some(ref old_sigwinch) = self.old_sigwinch {
+            unsafe { sigaction(NixSignal::SIGWINCH, old_sigwinch)?; }
+        }
 
         Ok(())
     }
@@ -142,6 +153,7 @@ impl Terminal for UnixTerminal {
             cursor_right_n: get_str("cuf")?,
 
             resume: Cell::new(None),
+            input_decoder: RefCell::new(InputDecoder::new()),
         })
     }
 
@@ -299,6 +311,11 @@ impl Terminal for UnixTerminal {
                     sigaction(NixSignal::SIGQUIT, &action)?
                 });
             }
+            if report_signals.contains(Signal::Resize) {
+                guard.old_sigwinch = Some(unsafe {
+                    sigaction(NixSignal::SIGWINCH, &action)?
+                });
+            }
         };
 
         self.resume.set(Some((catch_signals, report_signals.clone())));
@@ -326,23 +343,9 @@ impl Terminal for UnixTerminal {
         Ok(TerminalGuard::new(old_tio))
     }
 
-    fn read(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
-        buf.reserve(32);
-
-        let len = buf.len();
-        let cap = buf.capacity();
-        let n;
-
-        unsafe {
-            buf.set_len(cap);
-
-            let result = read_stdin(&mut buf[len..]);
-            buf.set_len(len);
-
-            n = result?;
-            buf.set_len(len + n);
-        }
-
+    fn read(&self, buf: &mut ReadBuffer) -> io::Result<usize> {
+        let n = read_stdin(buf.unfilled())?;
+        buf.advance(n);
         Ok(n)
     }
 
@@ -356,6 +359,13 @@ impl Terminal for UnixTerminal {
 }
 
 impl UnixTerminal {
+    /// Reads more bytes from the terminal and decodes them into key
+    /// events, resuming any escape sequence the previous call left
+    /// partway through.
+    pub fn read_keys(&self) -> io::Result<Vec<KeyEvent>> {
+        decoder::read_keys(self, &mut self.input_decoder.borrow_mut())
+    }
+
     fn resume(&self) {
         if let Some((catch_signals, report_signals)) = self.resume.take() {
             // prepare will reset this, but we want the Reader to see it.
@@ -370,8 +380,10 @@ impl UnixTerminal {
     }
 }
 
-fn read_stdin(buf: &mut [u8]) -> io::Result<usize> {
+fn read_stdin(buf: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
     retry(|| {
+        // read(2) writes into this region without requiring it to be
+        // initialized first, so handing it the raw pointer is sound.
         let res = unsafe { read(STDIN_FILENO,
             buf.as_mut_ptr() as *mut c_void, buf.len() as size_t) };
 
@@ -425,6 +437,7 @@ fn conv_signal(n: usize) -> Option<Signal> {
             Some(NixSignal::SIGINT)  => Some(Signal::Interrupt),
             Some(NixSignal::SIGTSTP) => Some(Signal::Suspend),
             Some(NixSignal::SIGQUIT) => Some(Signal::Quit),
+            Some(NixSignal::SIGWINCH) => Some(Signal::Resize),
             _ => None
         }
     }